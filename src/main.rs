@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use flate2::read::{DeflateDecoder, DeflateEncoder};
+use flate2::Compression;
 use std::fmt;
 use std::fs;
 use std::io::prelude::*;
@@ -28,6 +30,10 @@ enum Commands {
     /// Output file
     #[clap(short, long)]
     output: String,
+
+    /// Where to hide the payload: pixel LSBs, a private PNG chunk or a custom EXIF tag
+    #[clap(short, long, value_enum, default_value_t = Mode::Pixel)]
+    mode: Mode,
   },
   /// Decodes Data
   #[clap(arg_required_else_help = true)]
@@ -44,17 +50,107 @@ enum Commands {
 
 type Image = image::ImageBuffer<image::Rgba<u8>, std::vec::Vec<u8>>;
 
+/// Where the payload is hidden within the carrier image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+  /// Embed in the least-significant bits of the pixels.
+  Pixel,
+  /// Store in a private ancillary PNG chunk (`stEg`), leaving pixels untouched.
+  Chunk,
+  /// Store in a custom EXIF tag within the JPEG APP1 segment.
+  Exif,
+}
+
+// Private ancillary PNG chunk type used by `--mode chunk`.
+const STEG_CHUNK_TYPE: [u8; 4] = *b"stEg";
+
+// Private EXIF tag used by `--mode exif`, in the reserved private-tag range.
+const EXIF_STEG_TAG: u16 = 0xEA1C;
+
+/// Errors surfaced by the encode/decode pipeline.
+#[non_exhaustive]
+#[derive(Debug)]
+enum Error {
+  /// The input file could not be decoded as an image.
+  NotAnImage,
+  /// The carrier image cannot hold the header plus payload.
+  ImageTooSmall,
+  /// A decoded file name was not valid UTF-8.
+  BadUtf8Name,
+  /// The header declares a name+data length larger than the image can store.
+  DeclaredLengthExceedsCapacity,
+  /// A stored checksum did not match the recovered payload.
+  CorruptPayload,
+  /// The requested output format would destroy the embedded data (e.g. JPEG).
+  LossyOutputFormat,
+  /// `--mode chunk` was requested for a non-PNG output.
+  ChunkModeRequiresPng,
+  /// `--mode exif` was requested for a non-JPEG output.
+  ExifModeRequiresJpeg,
+  /// An underlying I/O operation failed.
+  IoError(std::io::Error),
+}
+
+type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::NotAnImage => write!(f, "input could not be decoded as an image"),
+      Error::ImageTooSmall => write!(f, "image is too small to fit the data"),
+      Error::BadUtf8Name => write!(f, "decoded file name is not valid UTF-8"),
+      Error::DeclaredLengthExceedsCapacity => {
+        write!(f, "header declares more data than the image can hold")
+      }
+      Error::CorruptPayload => write!(f, "checksum mismatch: the image has been corrupted"),
+      Error::LossyOutputFormat => write!(
+        f,
+        "output format is lossy and would destroy the hidden data; use a PNG output instead"
+      ),
+      Error::ChunkModeRequiresPng => write!(f, "--mode chunk is only supported for PNG outputs"),
+      Error::ExifModeRequiresJpeg => write!(f, "--mode exif is only supported for JPEG outputs"),
+      Error::IoError(err) => write!(f, "io error: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+  fn from(err: std::io::Error) -> Self {
+    Error::IoError(err)
+  }
+}
+
 // Pos  Length     Field
-// 0    1 byte   : Flags (unused)
+// 0    1 byte   : Flags (codec id: 0 = store, 1 = deflate, 2 = packbits)
 // 1    4 byte   : Name length
-// 2    4 byte   : Length of data in bytes
-// 6    16 bytes : Salt for encryption  (unused)
-// 22   X bytes  : Name
-// X+22 Y bytes  : Data
+// 5    4 byte   : Length of data in bytes
+// 9    4 bytes  : CRC-32 of the stored payload
+// 13   4 bytes  : Adler-32 of the decompressed name + data
+// 17   8 bytes  : Salt for encryption  (unused)
+// 25   X bytes  : Name
+// X+25 Y bytes  : Data
+
+// Codec ids stored in the Flags byte of the Header
+const CODEC_STORE: u8 = 0;
+const CODEC_DEFLATE: u8 = 1;
+const CODEC_PACKBITS: u8 = 2;
+
+fn codec_name(codec: u8) -> &'static str {
+  match codec {
+    CODEC_DEFLATE => "deflate",
+    CODEC_PACKBITS => "packbits",
+    _ => "store",
+  }
+}
 
 struct Header {
+  flags: u8,
   name_length: u32,
   data_length: u32,
+  crc32: u32,
+  adler32: u32,
 }
 
 struct FileData {
@@ -66,30 +162,31 @@ impl fmt::Debug for Header {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(
       f,
-      "Header {{ name_length: {}, data_length: {} }}",
-      self.name_length, self.data_length
+      "Header {{ flags: {}, name_length: {}, data_length: {}, crc32: {:#010x}, adler32: {:#010x} }}",
+      self.flags, self.name_length, self.data_length, self.crc32, self.adler32
     )
   }
 }
 
 // Byte cursor is incremented for every byte written to the file
 
-fn write_byte_vector_to_image(img: &mut Image, pixel_cursor: &mut u32, bytes: &Vec<u8>) {
+fn write_byte_vector_to_image(img: &mut Image, pixel_cursor: &mut u32, bytes: &Vec<u8>) -> Result<()> {
   for byte in bytes {
-    write_byte_to_image(img, pixel_cursor, &byte)
+    write_byte_to_image(img, pixel_cursor, &byte)?
   }
+  Ok(())
 }
 
 // TODO take into account multiple rows of pixels
-fn get_pixel_position(img: &Image, pixel_index: &u32) -> (u32, u32) {
+fn get_pixel_position(img: &Image, pixel_index: &u32) -> Result<(u32, u32)> {
   let y = ((pixel_index / img.width()) as f64).floor() as u32;
   let x = pixel_index % img.width();
 
-  if x > img.width() || y > img.height() {
-    panic!("Pixel index out of bounds");
+  if x >= img.width() || y >= img.height() {
+    return Err(Error::DeclaredLengthExceedsCapacity);
   }
 
-  (x, y)
+  Ok((x, y))
 }
 
 fn byte_with_x_last_bit(byte: &u8, x: u8) -> u8 {
@@ -105,7 +202,7 @@ fn get_last_bit_of_byte(byte: &u8) -> u8 {
 }
 
 // Write one byte (u8) to two pixels from a start pos
-fn write_byte_to_image(img: &mut Image, pixel_cursor: &mut u32, byte: &u8) {
+fn write_byte_to_image(img: &mut Image, pixel_cursor: &mut u32, byte: &u8) -> Result<()> {
   let mut bits: Vec<u8> = Vec::new();
 
   for bit_index in 0..8 {
@@ -114,7 +211,7 @@ fn write_byte_to_image(img: &mut Image, pixel_cursor: &mut u32, byte: &u8) {
   }
 
   for i in (0..8).step_by(4) {
-    let position = get_pixel_position(&img, pixel_cursor);
+    let position = get_pixel_position(&img, pixel_cursor)?;
     let existing_pixel = img[position];
 
     let new_pixel = image::Rgba([
@@ -128,13 +225,15 @@ fn write_byte_to_image(img: &mut Image, pixel_cursor: &mut u32, byte: &u8) {
 
     img.put_pixel(position.0, position.1, new_pixel);
   }
+
+  Ok(())
 }
 
-fn read_byte_from_image(img: &Image, pixel_cursor: &mut u32) -> u8 {
+fn read_byte_from_image(img: &Image, pixel_cursor: &mut u32) -> Result<u8> {
   let mut byte: u8 = 0;
 
   for i in (0..8).step_by(4) {
-    let position = get_pixel_position(&img, pixel_cursor);
+    let position = get_pixel_position(&img, pixel_cursor)?;
     let existing_pixel = img[position];
     byte |= (get_last_bit_of_byte(&existing_pixel[0])) << i;
     byte |= (get_last_bit_of_byte(&existing_pixel[1])) << (i + 1);
@@ -144,22 +243,22 @@ fn read_byte_from_image(img: &Image, pixel_cursor: &mut u32) -> u8 {
     *pixel_cursor += 1;
   }
 
-  byte
+  Ok(byte)
 }
 
-fn read_bytes_from_image(img: &Image, pixel_cursor: &mut u32, length: &u32) -> Vec<u8> {
+fn read_bytes_from_image(img: &Image, pixel_cursor: &mut u32, length: &u32) -> Result<Vec<u8>> {
   let mut bytes: Vec<u8> = Vec::new();
 
   for _i in 0..*length {
-    let byte = read_byte_from_image(img, pixel_cursor);
+    let byte = read_byte_from_image(img, pixel_cursor)?;
     bytes.push(byte);
   }
 
-  bytes
+  Ok(bytes)
 }
 
-fn construct_string_from_byte_vector(bytes: &Vec<u8>) -> String {
-  String::from_utf8(bytes.to_vec()).unwrap()
+fn construct_string_from_byte_vector(bytes: &Vec<u8>) -> Result<String> {
+  String::from_utf8(bytes.to_vec()).map_err(|_| Error::BadUtf8Name)
 }
 
 fn convert_string_to_bytes(s: &String) -> Vec<u8> {
@@ -177,120 +276,790 @@ fn convert_byte_vector_to_u32(bytes: &Vec<u8>) -> u32 {
     | (bytes[3] as u32)
 }
 
-fn write_header(img: &mut Image, data: &Vec<u8>, name: &Vec<u8>, pixel_cursor: &mut u32) {
-  write_byte_to_image(img, pixel_cursor, &0);
-  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(name.len() as u32)); // 4 bytes
-  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(data.len() as u32)); // 4 bytes
-  write_byte_vector_to_image(img, pixel_cursor, &vec![0; 16]); // 16 bytes
+// Table-based CRC-32 (IEEE, polynomial 0xEDB88320) as used by PNG/zlib.
+fn crc32(data: &[u8]) -> u32 {
+  let mut table = [0u32; 256];
+  for n in 0..256u32 {
+    let mut c = n;
+    for _ in 0..8 {
+      if c & 1 == 1 {
+        c = 0xEDB8_8320 ^ (c >> 1);
+      } else {
+        c >>= 1;
+      }
+    }
+    table[n as usize] = c;
+  }
+
+  let mut value: u32 = 0xFFFF_FFFF;
+  for &byte in data {
+    value = table[((value ^ byte as u32) & 0xFF) as usize] ^ (value >> 8);
+  }
+  value ^ 0xFFFF_FFFF
+}
+
+// Adler-32 rolling checksum (two sums modulo 65521).
+fn adler32(data: &[u8]) -> u32 {
+  let mut a: u32 = 1;
+  let mut b: u32 = 0;
+  for &byte in data {
+    a = (a + byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+  (b << 16) | a
+}
+
+fn deflate_compress(data: &[u8]) -> Result<Vec<u8>> {
+  let mut encoder = DeflateEncoder::new(data, Compression::best());
+  let mut out = Vec::new();
+  encoder.read_to_end(&mut out)?;
+  Ok(out)
 }
 
-fn read_header(img: &Image, pixel_cursor: &mut u32) -> Header {
-  let _flags = read_byte_from_image(img, pixel_cursor);
-  let name_length_vec = read_bytes_from_image(img, pixel_cursor, &4);
-  let data_length_vec = read_bytes_from_image(img, pixel_cursor, &4);
-  let _salt = read_bytes_from_image(img, pixel_cursor, &16);
+fn deflate_decompress(data: &[u8]) -> Result<Vec<u8>> {
+  let mut decoder = DeflateDecoder::new(data);
+  let mut out = Vec::new();
+  // A hostile image can carry a valid CRC over a malformed stream, so surface a
+  // corruption error instead of panicking.
+  decoder
+    .read_to_end(&mut out)
+    .map_err(|_| Error::CorruptPayload)?;
+  Ok(out)
+}
+
+// PackBits RLE (TIFF flavour): a signed length byte `n` where 0..=127 copies the
+// next n+1 literal bytes, -1..=-127 repeats the next byte 1-n times, and -128 is
+// a no-op.
+fn packbits_encode(data: &[u8]) -> Vec<u8> {
+  let mut out: Vec<u8> = Vec::new();
+  let mut i = 0;
+
+  while i < data.len() {
+    let mut run = 1;
+    while i + run < data.len() && data[i + run] == data[i] && run < 128 {
+      run += 1;
+    }
+
+    if run >= 2 {
+      out.push(((1 - run as i32) as i8) as u8);
+      out.push(data[i]);
+      i += run;
+    } else {
+      let start = i;
+      let mut literals = 0;
+      while i < data.len() && literals < 128 {
+        if i + 1 < data.len() && data[i + 1] == data[i] {
+          break;
+        }
+        i += 1;
+        literals += 1;
+      }
+      out.push(((literals - 1) as i8) as u8);
+      out.extend_from_slice(&data[start..start + literals]);
+    }
+  }
+
+  out
+}
+
+fn packbits_decode(data: &[u8]) -> Vec<u8> {
+  let mut out: Vec<u8> = Vec::new();
+  let mut i = 0;
+
+  while i < data.len() {
+    let n = data[i] as i8;
+    i += 1;
+
+    if n >= 0 {
+      let count = n as usize + 1;
+      out.extend_from_slice(&data[i..i + count]);
+      i += count;
+    } else if n != -128 {
+      let count = (1 - n as i32) as usize;
+      let byte = data[i];
+      i += 1;
+      for _ in 0..count {
+        out.push(byte);
+      }
+    }
+  }
+
+  out
+}
+
+// Pick the smallest of store/deflate/packbits and return the codec id alongside
+// the encoded payload.
+fn compress_payload(data: &Vec<u8>) -> Result<(u8, Vec<u8>)> {
+  let deflated = deflate_compress(data)?;
+  let packed = packbits_encode(data);
 
-  Header {
+  let mut codec = CODEC_STORE;
+  let mut best = data.len();
+
+  if deflated.len() < best {
+    codec = CODEC_DEFLATE;
+    best = deflated.len();
+  }
+  if packed.len() < best {
+    codec = CODEC_PACKBITS;
+  }
+
+  Ok(match codec {
+    CODEC_DEFLATE => (CODEC_DEFLATE, deflated),
+    CODEC_PACKBITS => (CODEC_PACKBITS, packed),
+    _ => (CODEC_STORE, data.clone()),
+  })
+}
+
+fn decompress_payload(codec: u8, data: &Vec<u8>) -> Result<Vec<u8>> {
+  Ok(match codec {
+    CODEC_DEFLATE => deflate_decompress(data)?,
+    CODEC_PACKBITS => packbits_decode(data),
+    _ => data.clone(),
+  })
+}
+
+fn write_header(
+  img: &mut Image,
+  flags: u8,
+  crc32: u32,
+  adler32: u32,
+  data: &Vec<u8>,
+  name: &Vec<u8>,
+  pixel_cursor: &mut u32,
+) -> Result<()> {
+  write_byte_to_image(img, pixel_cursor, &flags)?;
+  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(name.len() as u32))?; // 4 bytes
+  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(data.len() as u32))?; // 4 bytes
+  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(crc32))?; // 4 bytes
+  write_byte_vector_to_image(img, pixel_cursor, &convert_u32_to_bytes(adler32))?; // 4 bytes
+  write_byte_vector_to_image(img, pixel_cursor, &vec![0; 8])?; // 8 bytes salt
+  Ok(())
+}
+
+fn read_header(img: &Image, pixel_cursor: &mut u32) -> Result<Header> {
+  let flags = read_byte_from_image(img, pixel_cursor)?;
+  let name_length_vec = read_bytes_from_image(img, pixel_cursor, &4)?;
+  let data_length_vec = read_bytes_from_image(img, pixel_cursor, &4)?;
+  let crc32_vec = read_bytes_from_image(img, pixel_cursor, &4)?;
+  let adler32_vec = read_bytes_from_image(img, pixel_cursor, &4)?;
+  let _salt = read_bytes_from_image(img, pixel_cursor, &8)?;
+
+  Ok(Header {
+    flags,
     name_length: convert_byte_vector_to_u32(&name_length_vec),
     data_length: convert_byte_vector_to_u32(&data_length_vec),
-  }
+    crc32: convert_byte_vector_to_u32(&crc32_vec),
+    adler32: convert_byte_vector_to_u32(&adler32_vec),
+  })
 }
 
-fn get_data_bytes_from_file(file_path: &str) -> Vec<u8> {
-  let mut file = fs::File::open(file_path).unwrap();
+// Number of bytes consumed by the fixed header before the name/data payload, the
+// sum of the fields that `write_header`, `read_header` and `serialize_payload` handle:
+// flags (1) + name length (4) + data length (4) + CRC-32 (4) + Adler-32 (4) + salt (8).
+const HEADER_BYTES: u32 = 1 + 4 + 4 + 4 + 4 + 8;
+// Each payload byte is spread across two pixels (four channels per pixel).
+const PIXELS_PER_BYTE: u32 = 2;
+
+// Pixels required to store the header plus the declared name and data, mirroring
+// minipng's `required_bytes()` precheck against the available buffer. Computed in
+// u64 so a hostile length field cannot overflow the multiplication.
+fn required_pixels(header: &Header) -> u64 {
+  (HEADER_BYTES as u64 + header.name_length as u64 + header.data_length as u64)
+    * PIXELS_PER_BYTE as u64
+}
+
+fn get_data_bytes_from_file(file_path: &str) -> Result<Vec<u8>> {
+  let mut file = fs::File::open(file_path)?;
   let mut data = Vec::new();
-  file.read_to_end(&mut data).unwrap();
-  data
+  file.read_to_end(&mut data)?;
+  Ok(data)
 }
 
 fn get_image_capacity(img: &Image) -> u32 {
-  img.height() * img.width() - 1000 // Remove 1000 for the header
+  // Compute in u64 and saturate so tiny carriers don't underflow and huge ones
+  // don't overflow; a zero result drives the `ImageTooSmall` guard in `encode`.
+  let pixels = img.width() as u64 * img.height() as u64;
+  pixels.saturating_sub(1000).min(u32::MAX as u64) as u32 // Remove 1000 for the header
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// True unless the output extension is a known lossless, full-colour pixel format.
+// An allow-list is safer than an ever-growing deny-list: anything that quantizes
+// (gif), re-compresses (jpeg/webp), or is otherwise unknown destroys the LSBs.
+fn is_lossy_output(output_path: &str) -> bool {
+  !matches!(
+    output_path.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref(),
+    Some("png" | "bmp" | "tiff" | "tif" | "tga")
+  )
+}
+
+fn is_png_output(output_path: &str) -> bool {
+  matches!(
+    output_path.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref(),
+    Some("png")
+  )
+}
+
+// Splice a new chunk in immediately before IEND, walking the chunk stream the way
+// minipng walks IHDR/IDAT/IEND: 4-byte big-endian length, 4-byte type, data, CRC.
+fn splice_png_chunk(png_bytes: &[u8], chunk_type: &[u8; 4], data: &[u8]) -> Result<Vec<u8>> {
+  if png_bytes.len() < 8 || png_bytes[..8] != PNG_SIGNATURE {
+    return Err(Error::NotAnImage);
+  }
+
+  let mut pos = 8;
+  loop {
+    if pos + 8 > png_bytes.len() {
+      return Err(Error::NotAnImage);
+    }
+    let length = convert_byte_vector_to_u32(&png_bytes[pos..pos + 4].to_vec()) as usize;
+    if &png_bytes[pos + 4..pos + 8] == b"IEND" {
+      break;
+    }
+    pos += 12 + length;
+  }
+
+  let mut chunk: Vec<u8> = Vec::new();
+  chunk.extend_from_slice(&convert_u32_to_bytes(data.len() as u32));
+  chunk.extend_from_slice(chunk_type);
+  chunk.extend_from_slice(data);
+  let mut crc_input: Vec<u8> = Vec::new();
+  crc_input.extend_from_slice(chunk_type);
+  crc_input.extend_from_slice(data);
+  chunk.extend_from_slice(&convert_u32_to_bytes(crc32(&crc_input)));
+
+  let mut out: Vec<u8> = Vec::with_capacity(png_bytes.len() + chunk.len());
+  out.extend_from_slice(&png_bytes[..pos]);
+  out.extend_from_slice(&chunk);
+  out.extend_from_slice(&png_bytes[pos..]);
+  Ok(out)
 }
 
-fn encode_data(img: &mut Image, data: &Vec<u8>, name: &Vec<u8>) {
+fn is_jpeg_output(output_path: &str) -> bool {
+  matches!(
+    output_path.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref(),
+    Some("jpg" | "jpeg" | "jfif")
+  )
+}
+
+fn read_u16_order(bytes: &[u8], offset: usize, little_endian: bool) -> u16 {
+  if little_endian {
+    (bytes[offset] as u16) | ((bytes[offset + 1] as u16) << 8)
+  } else {
+    ((bytes[offset] as u16) << 8) | (bytes[offset + 1] as u16)
+  }
+}
+
+fn read_u32_order(bytes: &[u8], offset: usize, little_endian: bool) -> u32 {
+  if little_endian {
+    (bytes[offset] as u32)
+      | ((bytes[offset + 1] as u32) << 8)
+      | ((bytes[offset + 2] as u32) << 16)
+      | ((bytes[offset + 3] as u32) << 24)
+  } else {
+    ((bytes[offset] as u32) << 24)
+      | ((bytes[offset + 1] as u32) << 16)
+      | ((bytes[offset + 2] as u32) << 8)
+      | (bytes[offset + 3] as u32)
+  }
+}
+
+// Pull the TIFF block out of a JPEG's APP1/EXIF segment (the bytes following the
+// "Exif\0\0" identifier), walking the JPEG marker segments up to the scan start.
+fn extract_jpeg_exif(bytes: &[u8]) -> Option<Vec<u8>> {
+  if bytes.len() < 2 || bytes[0] != 0xFF || bytes[1] != 0xD8 {
+    return None;
+  }
+
+  let mut pos = 2;
+  while pos + 4 <= bytes.len() {
+    if bytes[pos] != 0xFF {
+      return None;
+    }
+    let marker = bytes[pos + 1];
+    if marker == 0xDA {
+      break; // start of scan — no more metadata segments
+    }
+    let seg_len = ((bytes[pos + 2] as usize) << 8) | (bytes[pos + 3] as usize);
+    let seg_end = pos + 2 + seg_len;
+    if seg_end > bytes.len() {
+      return None;
+    }
+    if marker == 0xE1 {
+      let data = &bytes[pos + 4..seg_end];
+      if data.len() >= 6 && &data[..6] == b"Exif\0\0" {
+        return Some(data[6..].to_vec());
+      }
+    }
+    pos = seg_end;
+  }
+
+  None
+}
+
+// Walk the IFDs of a TIFF/EXIF block and return the value bytes of the given tag.
+// Reads the byte order and 0x002A magic from the TIFF header, then decodes each
+// 12-byte IFD entry (tag, type, count, value/offset).
+fn find_exif_tag_data(tiff: &[u8], tag: u16) -> Option<Vec<u8>> {
+  if tiff.len() < 8 {
+    return None;
+  }
+  let little_endian = match &tiff[0..2] {
+    b"II" => true,
+    b"MM" => false,
+    _ => return None,
+  };
+  if read_u16_order(tiff, 2, little_endian) != 0x002A {
+    return None;
+  }
+
+  // Follow the IFD chain so a tag merged in as a trailing IFD is still found.
+  let mut ifd = read_u32_order(tiff, 4, little_endian) as usize;
+  let mut guard = 0;
+  while ifd != 0 && guard < 16 {
+    guard += 1;
+    if ifd + 2 > tiff.len() {
+      return None;
+    }
+    let entry_count = read_u16_order(tiff, ifd, little_endian) as usize;
+
+    for i in 0..entry_count {
+      let entry = ifd + 2 + i * 12;
+      if entry + 12 > tiff.len() {
+        return None;
+      }
+      let entry_tag = read_u16_order(tiff, entry, little_endian);
+      let entry_type = read_u16_order(tiff, entry + 2, little_endian);
+      let count = read_u32_order(tiff, entry + 4, little_endian) as usize;
+      let value_offset = read_u32_order(tiff, entry + 8, little_endian) as usize;
+      if entry_tag == tag {
+        let unit = match entry_type {
+          3 | 8 => 2,
+          4 | 9 | 11 => 4,
+          5 | 10 | 12 => 8,
+          _ => 1,
+        };
+        let size = count * unit;
+        // Values of four bytes or fewer are stored inline in the entry itself.
+        let start = if size <= 4 { entry + 8 } else { value_offset };
+        if start + size > tiff.len() {
+          return None;
+        }
+        return Some(tiff[start..start + size].to_vec());
+      }
+    }
+
+    let next_ptr = ifd + 2 + entry_count * 12;
+    if next_ptr + 4 > tiff.len() {
+      return None;
+    }
+    ifd = read_u32_order(tiff, next_ptr, little_endian) as usize;
+  }
+
+  None
+}
+
+fn write_u16_order(out: &mut Vec<u8>, value: u16, little_endian: bool) {
+  if little_endian {
+    out.extend_from_slice(&value.to_le_bytes());
+  } else {
+    out.extend_from_slice(&value.to_be_bytes());
+  }
+}
+
+fn write_u32_order(out: &mut Vec<u8>, value: u32, little_endian: bool) {
+  if little_endian {
+    out.extend_from_slice(&value.to_le_bytes());
+  } else {
+    out.extend_from_slice(&value.to_be_bytes());
+  }
+}
+
+// Append `payload` under `tag` to an existing TIFF/EXIF block as a trailing IFD,
+// chained off the last IFD's next-pointer, so the original camera metadata is kept
+// intact. Falls back to a fresh block if the source can't be parsed.
+fn merge_exif_tag(source: &[u8], tag: u16, payload: &[u8]) -> Vec<u8> {
+  if source.len() < 8 {
+    return build_exif_block(tag, payload);
+  }
+  let little_endian = match &source[0..2] {
+    b"II" => true,
+    b"MM" => false,
+    _ => return build_exif_block(tag, payload),
+  };
+  if read_u16_order(source, 2, little_endian) != 0x002A {
+    return build_exif_block(tag, payload);
+  }
+
+  // Walk to the last IFD in the chain, whose next-pointer we will repoint.
+  let mut next_ptr_pos;
+  let mut ifd = read_u32_order(source, 4, little_endian) as usize;
+  let mut guard = 0;
+  loop {
+    guard += 1;
+    if guard > 16 || ifd + 2 > source.len() {
+      return build_exif_block(tag, payload);
+    }
+    let entry_count = read_u16_order(source, ifd, little_endian) as usize;
+    next_ptr_pos = ifd + 2 + entry_count * 12;
+    if next_ptr_pos + 4 > source.len() {
+      return build_exif_block(tag, payload);
+    }
+    let next = read_u32_order(source, next_ptr_pos, little_endian) as usize;
+    if next == 0 {
+      break;
+    }
+    ifd = next;
+  }
+
+  let mut block = source.to_vec();
+  let new_ifd_offset = block.len();
+  let value_offset = (new_ifd_offset + 2 + 12 + 4) as u32; // count + entry + next pointer
+
+  write_u16_order(&mut block, 1, little_endian); // one entry
+  write_u16_order(&mut block, tag, little_endian);
+  write_u16_order(&mut block, 7, little_endian); // type UNDEFINED
+  write_u32_order(&mut block, payload.len() as u32, little_endian);
+  write_u32_order(&mut block, value_offset, little_endian);
+  write_u32_order(&mut block, 0, little_endian); // no further IFD
+  block.extend_from_slice(payload);
+
+  // Repoint the last IFD's next-pointer at our appended IFD.
+  let mut pointer: Vec<u8> = Vec::new();
+  write_u32_order(&mut pointer, new_ifd_offset as u32, little_endian);
+  block[next_ptr_pos..next_ptr_pos + 4].copy_from_slice(&pointer);
+
+  block
+}
+
+// Build a minimal little-endian TIFF/EXIF block holding `payload` in a single
+// IFD0 entry of type UNDEFINED under the given tag.
+fn build_exif_block(tag: u16, payload: &[u8]) -> Vec<u8> {
+  let mut tiff: Vec<u8> = Vec::new();
+  tiff.extend_from_slice(b"II");
+  tiff.extend_from_slice(&0x002Au16.to_le_bytes());
+  tiff.extend_from_slice(&8u32.to_le_bytes()); // IFD0 starts right after the header
+
+  tiff.extend_from_slice(&1u16.to_le_bytes()); // one entry
+  tiff.extend_from_slice(&tag.to_le_bytes());
+  tiff.extend_from_slice(&7u16.to_le_bytes()); // type UNDEFINED
+  tiff.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+  let value_offset: u32 = 8 + 2 + 12 + 4; // header + count + entry + next-IFD pointer
+  tiff.extend_from_slice(&value_offset.to_le_bytes());
+  tiff.extend_from_slice(&0u32.to_le_bytes()); // no next IFD
+  tiff.extend_from_slice(payload);
+
+  tiff
+}
+
+// Insert an APP1/EXIF segment carrying `tiff` immediately after the JPEG SOI.
+fn splice_jpeg_exif(jpeg: &[u8], tiff: &[u8]) -> Result<Vec<u8>> {
+  if jpeg.len() < 2 || jpeg[0] != 0xFF || jpeg[1] != 0xD8 {
+    return Err(Error::NotAnImage);
+  }
+
+  // A JPEG marker segment length field is a single u16 (and includes its own two
+  // bytes), so the EXIF block can't be hidden in one APP1 beyond this ceiling.
+  let seg_len = 2 + 6 + tiff.len();
+  if seg_len > 0xFFFF {
+    return Err(Error::ImageTooSmall);
+  }
+
+  let mut app1: Vec<u8> = Vec::new();
+  app1.push(0xFF);
+  app1.push(0xE1);
+  app1.extend_from_slice(&(seg_len as u16).to_be_bytes());
+  app1.extend_from_slice(b"Exif\0\0");
+  app1.extend_from_slice(tiff);
+
+  let mut out: Vec<u8> = Vec::with_capacity(jpeg.len() + app1.len());
+  out.extend_from_slice(&jpeg[..2]);
+  out.extend_from_slice(&app1);
+  out.extend_from_slice(&jpeg[2..]);
+  Ok(out)
+}
+
+// Return the data of the first chunk of the given type, if present.
+fn find_png_chunk(png_bytes: &[u8], chunk_type: &[u8; 4]) -> Option<Vec<u8>> {
+  if png_bytes.len() < 8 || png_bytes[..8] != PNG_SIGNATURE {
+    return None;
+  }
+
+  let mut pos = 8;
+  while pos + 8 <= png_bytes.len() {
+    let length = convert_byte_vector_to_u32(&png_bytes[pos..pos + 4].to_vec()) as usize;
+    let ctype = &png_bytes[pos + 4..pos + 8];
+    let data_start = pos + 8;
+    let data_end = data_start + length;
+    if data_end > png_bytes.len() {
+      return None;
+    }
+    if ctype == chunk_type {
+      return Some(png_bytes[data_start..data_end].to_vec());
+    }
+    pos = data_end + 4; // skip CRC
+  }
+
+  None
+}
+
+fn encode_data(img: &mut Image, flags: u8, data: &Vec<u8>, name: &Vec<u8>) -> Result<()> {
   println!("Encoding image 🥷");
   let mut pixel_cursor: u32 = 0;
 
-  write_header(img, &data, &name, &mut pixel_cursor);
+  let payload_crc = crc32(data);
+  let mut name_and_data = name.clone();
+  name_and_data.extend_from_slice(&decompress_payload(flags, data)?);
+  let name_data_adler = adler32(&name_and_data);
+
+  write_header(
+    img,
+    flags,
+    payload_crc,
+    name_data_adler,
+    &data,
+    &name,
+    &mut pixel_cursor,
+  )?;
   println!("Encoded Header ✅");
 
-  write_byte_vector_to_image(img, &mut pixel_cursor, &name);
-  write_byte_vector_to_image(img, &mut pixel_cursor, &data);
+  write_byte_vector_to_image(img, &mut pixel_cursor, &name)?;
+  write_byte_vector_to_image(img, &mut pixel_cursor, &data)?;
   println!("Encoded Data ✅");
+
+  Ok(())
 }
 
-fn decode_data(img: &Image) -> FileData {
+fn decode_data(img: &Image) -> Result<FileData> {
   println!("Decoding image 🔎");
   let mut pixel_cursor: u32 = 0;
 
-  let header = read_header(img, &mut pixel_cursor);
+  let header = read_header(img, &mut pixel_cursor)?;
   println!("Decoded Header ✅");
 
-  let file_name_bytes = read_bytes_from_image(img, &mut pixel_cursor, &header.name_length);
-  let data_bytes = read_bytes_from_image(img, &mut pixel_cursor, &header.data_length);
+  let available_pixels = img.width() as u64 * img.height() as u64;
+  if required_pixels(&header) > available_pixels {
+    return Err(Error::DeclaredLengthExceedsCapacity);
+  }
+
+  let file_name_bytes = read_bytes_from_image(img, &mut pixel_cursor, &header.name_length)?;
+  let data_bytes = read_bytes_from_image(img, &mut pixel_cursor, &header.data_length)?;
   println!("Decoded Data ✅");
 
-  FileData {
-    name: construct_string_from_byte_vector(&file_name_bytes),
-    data: data_bytes,
+  finalize_file_data(&header, &file_name_bytes, &data_bytes)
+}
+
+// Verify checksums, decompress and validate the name for a recovered payload,
+// shared by the pixel- and chunk-embedding decode paths.
+fn finalize_file_data(header: &Header, name_bytes: &Vec<u8>, data_bytes: &Vec<u8>) -> Result<FileData> {
+  if crc32(data_bytes) != header.crc32 {
+    return Err(Error::CorruptPayload);
+  }
+
+  let data = decompress_payload(header.flags, data_bytes)?;
+
+  let mut name_and_data = name_bytes.clone();
+  name_and_data.extend_from_slice(&data);
+  if adler32(&name_and_data) != header.adler32 {
+    return Err(Error::CorruptPayload);
+  }
+  println!("Verified Checksums ✅");
+
+  Ok(FileData {
+    name: construct_string_from_byte_vector(name_bytes)?,
+    data,
+  })
+}
+
+// Serialize the fixed `HEADER_BYTES` header followed by name and data into a flat
+// buffer, for storage outside the pixel grid (e.g. a PNG ancillary chunk). The
+// read side (`deserialize_payload`) keys its name offset off the same constant.
+fn serialize_payload(flags: u8, data: &Vec<u8>, name: &Vec<u8>) -> Result<Vec<u8>> {
+  let payload_crc = crc32(data);
+  let mut name_and_data = name.clone();
+  name_and_data.extend_from_slice(&decompress_payload(flags, data)?);
+  let name_data_adler = adler32(&name_and_data);
+
+  let mut out: Vec<u8> = Vec::new();
+  out.push(flags);
+  out.extend_from_slice(&convert_u32_to_bytes(name.len() as u32));
+  out.extend_from_slice(&convert_u32_to_bytes(data.len() as u32));
+  out.extend_from_slice(&convert_u32_to_bytes(payload_crc));
+  out.extend_from_slice(&convert_u32_to_bytes(name_data_adler));
+  out.extend_from_slice(&vec![0; 8]);
+  out.extend_from_slice(name);
+  out.extend_from_slice(data);
+  Ok(out)
+}
+
+// Parse a buffer produced by `serialize_payload` back into a FileData.
+fn deserialize_payload(bytes: &[u8]) -> Result<FileData> {
+  if bytes.len() < HEADER_BYTES as usize {
+    return Err(Error::DeclaredLengthExceedsCapacity);
   }
+
+  let header = Header {
+    flags: bytes[0],
+    name_length: convert_byte_vector_to_u32(&bytes[1..5].to_vec()),
+    data_length: convert_byte_vector_to_u32(&bytes[5..9].to_vec()),
+    crc32: convert_byte_vector_to_u32(&bytes[9..13].to_vec()),
+    adler32: convert_byte_vector_to_u32(&bytes[13..17].to_vec()),
+  };
+
+  let name_start = HEADER_BYTES as usize;
+  let data_start = name_start + header.name_length as usize;
+  let data_end = data_start + header.data_length as usize;
+  if data_end > bytes.len() {
+    return Err(Error::DeclaredLengthExceedsCapacity);
+  }
+
+  let name_bytes = bytes[name_start..data_start].to_vec();
+  let data_bytes = bytes[data_start..data_end].to_vec();
+
+  finalize_file_data(&header, &name_bytes, &data_bytes)
 }
 
-fn encode(image_path: &String, data_path: &String, output_path: &String) {
+fn encode(image_path: &String, data_path: &String, output_path: &String, mode: Mode) -> Result<()> {
+  // A lossy output destroys LSBs and renormalizes pixels, so refuse it for the
+  // pixel-based modes. The exif mode hides data in metadata, so a lossy JPEG is
+  // exactly what it needs.
+  if mode != Mode::Exif && is_lossy_output(output_path) {
+    return Err(Error::LossyOutputFormat);
+  }
+  if mode == Mode::Chunk && !is_png_output(output_path) {
+    return Err(Error::ChunkModeRequiresPng);
+  }
+  if mode == Mode::Exif && !is_jpeg_output(output_path) {
+    return Err(Error::ExifModeRequiresJpeg);
+  }
+
+  // Keep the original EXIF around so a pixel-mode PNG stays plausible.
+  let source_exif = fs::read(image_path).ok().and_then(|b| extract_jpeg_exif(&b));
+
   let mut img = image::open(&image_path)
-    .expect("error reading image file")
+    .map_err(|_| Error::NotAnImage)?
     .to_rgba8();
 
-  let data = get_data_bytes_from_file(&data_path);
-  let percent_used = ((data.len() as f64) / (get_image_capacity(&img) as f64)) * 100.0;
+  let data = get_data_bytes_from_file(&data_path)?;
+  let (codec, payload) = compress_payload(&data)?;
+  let capacity = get_image_capacity(&img);
+  let percent_used = ((payload.len() as f64) / (capacity as f64)) * 100.0;
 
-  if percent_used > 99.9 {
-    println!("Image is too small to fit the data");
-    return;
+  if mode == Mode::Pixel && (capacity == 0 || percent_used > 99.9) {
+    return Err(Error::ImageTooSmall);
   }
 
   println!(
-    "Space used in image: {:.1}% Data Size: {:.1}MB",
+    "Space used in image: {:.1}% Data Size: {} bytes → {} bytes (codec {})",
     percent_used,
-    (data.len() as f64) / (1024.0 * 1024.0)
+    data.len(),
+    payload.len(),
+    codec_name(codec)
   );
 
   let file_name_without_initial_slashes = String::from(data_path.split("/").last().unwrap());
+  let name = convert_string_to_bytes(&file_name_without_initial_slashes);
+
+  match mode {
+    Mode::Pixel => {
+      encode_data(&mut img, codec, &payload, &name)?;
+      img.save(output_path).map_err(|_| Error::NotAnImage)?;
+
+      // Re-attach the source EXIF as a PNG eXIf chunk so camera metadata survives
+      // the to_rgba8 round-trip.
+      if is_png_output(output_path) {
+        if let Some(exif) = &source_exif {
+          let png_bytes = fs::read(output_path)?;
+          let spliced = splice_png_chunk(&png_bytes, b"eXIf", exif)?;
+          fs::write(output_path, spliced)?;
+          println!("Preserved EXIF ✅");
+        }
+      }
+    }
+    Mode::Exif => {
+      // Pixels are irrelevant in this mode — hide the payload in a custom EXIF tag
+      // within the JPEG APP1 segment. JPEG has no alpha channel and the encoder
+      // rejects Rgba8, so drop to RGB before saving.
+      image::DynamicImage::ImageRgba8(img.clone())
+        .to_rgb8()
+        .save(output_path)
+        .map_err(|_| Error::NotAnImage)?;
+      let jpeg_bytes = fs::read(output_path)?;
+      let serialized = serialize_payload(codec, &payload, &name)?;
+      // Merge the steg tag into the original EXIF so the camera metadata is kept
+      // and the image stays plausible; build a fresh block if there was none.
+      let tiff = match &source_exif {
+        Some(exif) => merge_exif_tag(exif, EXIF_STEG_TAG, &serialized),
+        None => build_exif_block(EXIF_STEG_TAG, &serialized),
+      };
+      let spliced = splice_jpeg_exif(&jpeg_bytes, &tiff)?;
+      fs::write(output_path, spliced)?;
+      println!("Encoded EXIF ✅");
+    }
+    Mode::Chunk => {
+      // Save the untouched image, then splice the payload into a private chunk so
+      // it survives lossless re-compression of the pixel data.
+      img.save(output_path).map_err(|_| Error::NotAnImage)?;
+      let png_bytes = fs::read(output_path)?;
+      let serialized = serialize_payload(codec, &payload, &name)?;
+      let spliced = splice_png_chunk(&png_bytes, &STEG_CHUNK_TYPE, &serialized)?;
+      fs::write(output_path, spliced)?;
+      println!("Encoded Chunk ✅");
+    }
+  }
 
-  encode_data(
-    &mut img,
-    &data,
-    &convert_string_to_bytes(&file_name_without_initial_slashes),
-  );
-
-  img.save(output_path).expect("error saving image");
+  Ok(())
 }
 
-fn decode(image_path: &String, output_path: Option<String>) {
-  let img = image::open(&image_path)
-    .expect("error reading image file")
-    .to_rgba8();
-  let file_data = decode_data(&img);
+fn decode(image_path: &String, output_path: Option<String>) -> Result<()> {
+  // Auto-detect the embedding mode: a private stEg chunk wins, then a custom EXIF
+  // tag, and finally the pixel LSBs.
+  let file_bytes = fs::read(image_path)?;
+  let exif_payload = extract_jpeg_exif(&file_bytes)
+    .and_then(|tiff| find_exif_tag_data(&tiff, EXIF_STEG_TAG));
+
+  let file_data = if let Some(chunk) = find_png_chunk(&file_bytes, &STEG_CHUNK_TYPE) {
+    println!("Decoding image 🔎 (chunk mode)");
+    deserialize_payload(&chunk)?
+  } else if let Some(payload) = exif_payload {
+    println!("Decoding image 🔎 (exif mode)");
+    deserialize_payload(&payload)?
+  } else {
+    let img = image::open(&image_path)
+      .map_err(|_| Error::NotAnImage)?
+      .to_rgba8();
+    decode_data(&img)?
+  };
 
   let file_name = match output_path {
     Some(output) => output,
     None => file_data.name,
   };
 
-  let mut file = fs::File::create(file_name).expect("error creating file");
-  file.write_all(&file_data.data).expect("error writing file");
+  let mut file = fs::File::create(file_name)?;
+  file.write_all(&file_data.data)?;
+
+  Ok(())
 }
 
 fn main() {
   let args = Cli::parse();
 
-  match args.command {
+  let result = match args.command {
     Commands::Encode {
       image,
       file,
       output,
-    } => encode(&image, &file, &output),
+      mode,
+    } => encode(&image, &file, &output, mode),
     Commands::Decode { image, output } => decode(&image, output),
+  };
+
+  if let Err(err) = result {
+    eprintln!("Error: {}", err);
+    std::process::exit(1);
   }
 }
 
@@ -316,11 +1085,11 @@ mod tests {
     for byte in 0..255 {
       // Write a byte to the image
       let mut write_pixel_cursor: u32 = 0;
-      write_byte_to_image(&mut img, &mut write_pixel_cursor, &byte);
+      write_byte_to_image(&mut img, &mut write_pixel_cursor, &byte).unwrap();
       assert_eq!(write_pixel_cursor, 2);
       // Read the byte back
       let mut read_pixel_cursor: u32 = 0;
-      let read_byte = read_byte_from_image(&img, &mut read_pixel_cursor);
+      let read_byte = read_byte_from_image(&img, &mut read_pixel_cursor).unwrap();
       assert_eq!(read_pixel_cursor, 2);
 
       assert_eq!(byte, read_byte);
@@ -333,11 +1102,11 @@ mod tests {
       .expect("error reading image file")
       .to_rgba8();
 
-    assert_eq!(get_pixel_position(&img, &0), (0, 0));
-    assert_eq!(get_pixel_position(&img, &10), (10, 0));
-    assert_eq!(get_pixel_position(&img, &1000), (232, 1));
-    assert_eq!(get_pixel_position(&img, &10000), (16, 13));
-    assert_eq!(get_pixel_position(&img, &100000), (160, 130));
+    assert_eq!(get_pixel_position(&img, &0).unwrap(), (0, 0));
+    assert_eq!(get_pixel_position(&img, &10).unwrap(), (10, 0));
+    assert_eq!(get_pixel_position(&img, &1000).unwrap(), (232, 1));
+    assert_eq!(get_pixel_position(&img, &10000).unwrap(), (16, 13));
+    assert_eq!(get_pixel_position(&img, &100000).unwrap(), (160, 130));
   }
 
   #[test]
@@ -346,15 +1115,131 @@ mod tests {
       .expect("error reading image file")
       .to_rgba8();
 
-    let data = get_data_bytes_from_file("test-data/data.txt");
+    let data = get_data_bytes_from_file("test-data/data.txt").unwrap();
     let name = "data.txt".to_string();
 
     let mut img_copy = img.clone();
-    encode_data(&mut img_copy, &data, &convert_string_to_bytes(&name));
+    let (codec, payload) = compress_payload(&data).unwrap();
+    encode_data(&mut img_copy, codec, &payload, &convert_string_to_bytes(&name)).unwrap();
 
-    let file_data = decode_data(&img_copy);
+    let file_data = decode_data(&img_copy).unwrap();
 
     assert_eq!(file_data.name, name);
     assert_eq!(file_data.data, data);
   }
+
+  #[test]
+  fn test_crc32_known_vectors() {
+    assert_eq!(crc32(b""), 0x0000_0000);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+  }
+
+  #[test]
+  fn test_adler32_known_vectors() {
+    assert_eq!(adler32(b""), 0x0000_0001);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+  }
+
+  #[test]
+  fn test_png_chunk_roundtrip() {
+    // A minimal PNG-shaped byte stream: signature followed by an empty IEND chunk.
+    let mut png: Vec<u8> = Vec::new();
+    png.extend_from_slice(&PNG_SIGNATURE);
+    png.extend_from_slice(&convert_u32_to_bytes(0)); // IEND length
+    png.extend_from_slice(b"IEND");
+    png.extend_from_slice(&convert_u32_to_bytes(crc32(b"IEND")));
+
+    let name = convert_string_to_bytes(&"secret.txt".to_string());
+    let data = b"hello packbits".to_vec();
+    let (codec, payload) = compress_payload(&data).unwrap();
+    let serialized = serialize_payload(codec, &payload, &name).unwrap();
+
+    let spliced = splice_png_chunk(&png, &STEG_CHUNK_TYPE, &serialized).unwrap();
+    let recovered = find_png_chunk(&spliced, &STEG_CHUNK_TYPE).unwrap();
+    let file_data = deserialize_payload(&recovered).unwrap();
+
+    assert_eq!(file_data.name, "secret.txt");
+    assert_eq!(file_data.data, data);
+  }
+
+  #[test]
+  fn test_exif_tag_roundtrip() {
+    let name = convert_string_to_bytes(&"note.txt".to_string());
+    let data = b"hidden in metadata".to_vec();
+    let (codec, payload) = compress_payload(&data).unwrap();
+    let serialized = serialize_payload(codec, &payload, &name).unwrap();
+
+    let tiff = build_exif_block(EXIF_STEG_TAG, &serialized);
+    let recovered = find_exif_tag_data(&tiff, EXIF_STEG_TAG).unwrap();
+    let file_data = deserialize_payload(&recovered).unwrap();
+
+    assert_eq!(file_data.name, "note.txt");
+    assert_eq!(file_data.data, data);
+
+    // A tag that was never written is not found.
+    assert!(find_exif_tag_data(&tiff, 0x0001).is_none());
+  }
+
+  #[test]
+  fn test_merge_exif_preserves_original() {
+    // A source block carrying the camera Make tag (0x010F).
+    let source = build_exif_block(0x010F, b"Canon");
+
+    let name = convert_string_to_bytes(&"note.txt".to_string());
+    let data = b"hidden but plausible".to_vec();
+    let (codec, payload) = compress_payload(&data).unwrap();
+    let serialized = serialize_payload(codec, &payload, &name).unwrap();
+
+    let merged = merge_exif_tag(&source, EXIF_STEG_TAG, &serialized);
+
+    // Original metadata survives the merge...
+    assert_eq!(find_exif_tag_data(&merged, 0x010F).unwrap(), b"Canon");
+    // ...and the steg payload is recoverable.
+    let recovered = find_exif_tag_data(&merged, EXIF_STEG_TAG).unwrap();
+    let file_data = deserialize_payload(&recovered).unwrap();
+    assert_eq!(file_data.name, "note.txt");
+    assert_eq!(file_data.data, data);
+  }
+
+  #[test]
+  fn test_exif_mode_end_to_end() {
+    let image = "test-data/cat.jpeg".to_string();
+    let file = "test-data/data.txt".to_string();
+    let output = "test-data/exif-e2e.jpg".to_string();
+    let decoded = "test-data/exif-e2e-decoded.txt".to_string();
+
+    encode(&image, &file, &output, Mode::Exif).unwrap();
+    decode(&output, Some(decoded.clone())).unwrap();
+
+    let expected = get_data_bytes_from_file(&file).unwrap();
+    let actual = get_data_bytes_from_file(&decoded).unwrap();
+    assert_eq!(actual, expected);
+
+    std::fs::remove_file(&output).ok();
+    std::fs::remove_file(&decoded).ok();
+  }
+
+  #[test]
+  fn test_jpeg_exif_extract() {
+    // SOI + APP1(Exif) + SOS, extract returns the TIFF block.
+    let tiff = build_exif_block(EXIF_STEG_TAG, b"payload");
+    let spliced = splice_jpeg_exif(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02], &tiff).unwrap();
+    assert_eq!(extract_jpeg_exif(&spliced).unwrap(), tiff);
+  }
+
+  #[test]
+  fn test_packbits_roundtrip() {
+    let cases: Vec<Vec<u8>> = vec![
+      vec![],
+      vec![1, 2, 3, 4, 5],
+      vec![7, 7, 7, 7, 7, 7, 7, 7],
+      vec![1, 1, 2, 3, 3, 3, 4, 5, 5],
+      vec![9; 300],
+    ];
+
+    for case in cases {
+      let encoded = packbits_encode(&case);
+      assert_eq!(packbits_decode(&encoded), case);
+    }
+  }
 }